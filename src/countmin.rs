@@ -0,0 +1,223 @@
+
+use std::f64::consts::E;
+
+// Fixed base seed for the per-row hash seeds.  Using a deterministic
+// seed (rather than one drawn from the task RNG) means any two
+// CountMinSketch instances built with the same width/depth derive the
+// exact same row seeds, and are therefore mergeable -- merging sketches
+// built from an RNG-drawn seed would silently add together counters that
+// refer to different buckets.
+static SEED_BASE: u64 = 0x9e3779b97f4a7c15u64;
+
+// A small, fixed-output splitmix64 step, used only to turn the row index
+// into a well-distributed seed; not used anywhere performance-critical.
+fn splitmix64(mut x: u64) -> u64 {
+    x += 0x9e3779b97f4a7c15u64;
+    x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9u64;
+    x = (x ^ (x >> 27)) * 0x94d049bb133111ebu64;
+    x ^ (x >> 31)
+}
+
+///
+/// Implements a Count-Min Sketch, an approximate frequency table.  Given
+/// error parameters epsilon and delta, the sketch answers point queries
+/// `estimate(x)` such that
+///
+///     actual(x) <= estimate(x) <= actual(x) + epsilon * N
+///
+/// with probability `1 - delta`, where N is the total number of items
+/// offered to the sketch.
+///
+/// Internally this is a `depth` x `width` array of u32 counters, where
+/// `width = ceil(e / epsilon)` and `depth = ceil(ln(1 / delta))`.  Each
+/// row uses its own hash seed, so a single incoming hash is combined with
+/// `depth` independent seeds (via double hashing) to select one counter
+/// per row.
+///
+/// See the [original paper](http://dimacs.rutgers.edu/~graham/pubs/papers/cm-full.pdf)
+/// for background.
+///
+pub struct CountMinSketch {
+    width: u32,
+    depth: u32,
+    counters: Vec<u32>,
+    seeds: Vec<u64>
+}
+
+impl CountMinSketch {
+
+    /// Construct a new CountMinSketch from error parameters.
+    ///
+    /// epsilon: the desired additive error factor (relative to N, the
+    ///          total count of items offered)
+    /// delta:   the probability that the error bound is violated
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let mut cms = CountMinSketch::new(0.01f64, 0.01f64);
+    ///```
+    #[experimental]
+    pub fn new(epsilon: f64, delta: f64) -> CountMinSketch {
+        let width = (E / epsilon).ceil() as u32;
+        let depth = (1f64 / delta).ln().ceil() as u32;
+
+        let counters: Vec<u32> = Vec::from_elem((width * depth) as uint, 0u32);
+
+        // deterministic row seeds: any two sketches built with the same
+        // depth derive identical seeds, so they remain mergeable
+        let seeds: Vec<u64> = range(0u32, depth).map(|i| splitmix64(SEED_BASE + i as u64)).collect();
+
+        CountMinSketch {width: width, depth: depth, counters: counters, seeds: seeds}
+    }
+
+    /// Compute the bucket index for row `row` of a given hash, via double
+    /// hashing: `h1 + row * h2 mod width`.  Computed inline rather than
+    /// gathered into a `Vec` up front, since `offer_hashed` /
+    /// `offer_hashed_conservative` / `estimate` are the hot ingestion and
+    /// query paths and shouldn't allocate per call.
+    fn index_for(&self, hash: &u64, row: u32) -> uint {
+        let h2 = (*hash >> 32) | 1;
+        let h1 = *hash ^ self.seeds[row as uint];
+        let row_offset = row * self.width;
+        let col = ((h1 + row as u64 * h2) % self.width as u64) as u32;
+        (row_offset + col) as uint
+    }
+
+    /// Offer a hashed u64 value to the sketch, incrementing one counter
+    /// per row.
+    #[experimental]
+    pub fn offer_hashed(&mut self, hash: &u64) {
+        let mut row = 0u32;
+        while row < self.depth {
+            let idx = self.index_for(hash, row);
+            *self.counters.get_mut(idx) += 1;
+            row += 1;
+        }
+    }
+
+    /// Offer a hashed u64 value using the conservative-update rule: a
+    /// counter is only raised to `1 + min`, rather than being
+    /// unconditionally incremented.  This reduces over-estimation in
+    /// practice at the cost of a second pass recomputing the same row
+    /// indices (cheap arithmetic, not a second hash or allocation).
+    #[experimental]
+    pub fn offer_hashed_conservative(&mut self, hash: &u64) {
+        let min = self.estimate(hash);
+
+        let mut row = 0u32;
+        while row < self.depth {
+            let idx = self.index_for(hash, row);
+            if self.counters[idx] < min + 1 {
+                *self.counters.get_mut(idx) = min + 1;
+            }
+            row += 1;
+        }
+    }
+
+    /// Returns the estimated frequency of the given hashed value.  The
+    /// estimate is always an over-estimate of the true count (or exact),
+    /// and is the minimum of the `depth` row counters for this hash.
+    #[experimental]
+    pub fn estimate(&self, hash: &u64) -> u32 {
+        let mut min = self.counters[self.index_for(hash, 0)];
+
+        let mut row = 1u32;
+        while row < self.depth {
+            let value = self.counters[self.index_for(hash, row)];
+            if value < min { min = value; }
+            row += 1;
+        }
+
+        min
+    }
+
+    /// Returns the amount of memory (in bytes) used by this data structure
+    #[experimental]
+    pub fn ram_bytes_used(&self) -> u32 {
+        8 + (self.width * self.depth * 4) + (self.depth * 8)    // width + depth + counters[u32] + seeds[u64]
+    }
+
+    /// Merge another sketch into this one by adding counters
+    /// position-wise.  Both sketches must have identical width and depth.
+    #[experimental]
+    pub fn merge(&mut self, other: &CountMinSketch) {
+        if self.width != other.width || self.depth != other.depth {
+            fail!("cannot merge CountMinSketch instances of differing dimensions")
+        }
+
+        let mut counter = 0u;
+        while counter < self.counters.len() {
+            *self.counters.get_mut(counter) += other.counters[counter];
+            counter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::CountMinSketch;
+    use std::hash;
+
+    #[test]
+    pub fn test_countmin_estimate() {
+        let mut cms = CountMinSketch::new(0.01f64, 0.01f64);
+
+        let hot = hash::hash(&"hot".to_string());
+        let cold = hash::hash(&"cold".to_string());
+
+        let mut count = 0u;
+        while count < 100 {
+            cms.offer_hashed(&hot);
+            count += 1;
+        }
+        cms.offer_hashed(&cold);
+
+        assert!(cms.estimate(&hot) >= 100);
+        assert!(cms.estimate(&cold) >= 1);
+    }
+
+    #[test]
+    pub fn test_countmin_merge() {
+        let mut cms1 = CountMinSketch::new(0.01f64, 0.01f64);
+        let mut cms2 = CountMinSketch::new(0.01f64, 0.01f64);
+
+        let value = hash::hash(&"shared".to_string());
+
+        let mut count = 0u;
+        while count < 40 {
+            cms1.offer_hashed(&value);
+            count += 1;
+        }
+        count = 0u;
+        while count < 60 {
+            cms2.offer_hashed(&value);
+            count += 1;
+        }
+
+        cms1.merge(&cms2);
+
+        assert!(cms1.estimate(&value) >= 100);
+    }
+
+    #[test]
+    pub fn test_countmin_conservative_never_overestimates_more_than_plain() {
+        let mut plain = CountMinSketch::new(0.01f64, 0.01f64);
+        let mut conservative = CountMinSketch::new(0.01f64, 0.01f64);
+
+        let value = hash::hash(&"value".to_string());
+        let other = hash::hash(&"other".to_string());
+
+        let mut count = 0u;
+        while count < 50 {
+            plain.offer_hashed(&value);
+            conservative.offer_hashed_conservative(&value);
+            count += 1;
+        }
+        plain.offer_hashed(&other);
+        conservative.offer_hashed_conservative(&other);
+
+        assert!(conservative.estimate(&value) <= plain.estimate(&value));
+    }
+}
@@ -0,0 +1,130 @@
+
+use std::hash::Hash;
+
+static SEED0: u64 = 0x16f11fe89b0d677cu64;
+static SEED1: u64 = 0xb480a793d8e6c86cu64;
+static SEED2: u64 = 0x6fe2e5aaf078ebc9u64;
+static SEED3: u64 = 0x14f994a4c5259381u64;
+
+static DIFFUSION: u64 = 0x2ab9c720d1655d21u64;
+
+fn rotl64(x: u64, n: uint) -> u64 {
+    (x << n) | (x >> (64 - n))
+}
+
+///
+/// A small, fast, non-cryptographic 64-bit hasher in the style of
+/// SeaHash.  It keeps four independent 64-bit lanes; each 8-byte block of
+/// input is folded into one lane (round-robin) with a multiply-xor-rotate
+/// step, and the four lanes are combined with the total input length and
+/// a final avalanche multiply to produce the finished hash.
+///
+/// This is dramatically faster than the cryptographic SipHash that
+/// `std::hash` defaults to, which is exactly what the bulk-ingestion
+/// sketches in this crate want: uniformly-distributed bits, as cheaply
+/// as possible.  It is not suitable for adversarial (HashDoS) contexts.
+///
+pub struct SeaHasher {
+    lanes: [u64, ..4],
+    index: uint,
+    length: u64,
+    buffer: Vec<u8>
+}
+
+impl SeaHasher {
+
+    /// Construct a new, empty SeaHasher state
+    pub fn new() -> SeaHasher {
+        SeaHasher {lanes: [SEED0, SEED1, SEED2, SEED3], index: 0, length: 0, buffer: Vec::new()}
+    }
+
+    fn write_block(&mut self, block: u64) {
+        let lane = self.index % 4;
+        self.lanes[lane] = rotl64(self.lanes[lane] ^ block, 29) * DIFFUSION;
+        self.index += 1;
+    }
+
+    /// Finish any buffered partial block (padded with zero) and return
+    /// the finished 64-bit hash.  Does not consume self, so a hasher
+    /// could in principle keep accumulating after this is called, though
+    /// `fast_hash` below always starts from a fresh instance.
+    pub fn finish(&mut self) -> u64 {
+        if self.buffer.len() > 0 {
+            let mut block: u64 = 0;
+            for (i, &b) in self.buffer.iter().enumerate() {
+                block |= (b as u64) << (i * 8);
+            }
+            self.write_block(block);
+            self.buffer.clear();
+        }
+
+        let mut result = self.lanes[0] ^ self.lanes[1] ^ self.lanes[2] ^ self.lanes[3];
+        result ^= self.length;
+        result *= DIFFUSION;
+        result ^= result >> 32;
+        result
+    }
+}
+
+impl Writer for SeaHasher {
+    fn write(&mut self, buf: &[u8]) {
+        self.length += buf.len() as u64;
+        self.buffer.push_all(buf);
+
+        while self.buffer.len() >= 8 {
+            let mut block: u64 = 0;
+            for i in range(0u, 8u) {
+                block |= (self.buffer[i] as u64) << (i * 8);
+            }
+            self.write_block(block);
+            self.buffer = self.buffer.slice_from(8).to_vec();
+        }
+    }
+}
+
+/// Hash a value with this crate's fast internal hasher, rather than the
+/// cryptographic (and much slower) SipHash that `std::hash` defaults to.
+/// Returns a 64-bit hash suitable for `offer_hashed`.
+///
+///### Usage
+///
+///```
+/// let hash = fasthash::fast_hash(&19u);
+///```
+pub fn fast_hash<T: Hash<SeaHasher>>(value: &T) -> u64 {
+    let mut state = SeaHasher::new();
+    value.hash(&mut state);
+    state.finish()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::fast_hash;
+
+    #[test]
+    pub fn test_fast_hash_deterministic() {
+        let value = "the quick brown fox".to_string();
+
+        assert_eq!(fast_hash(&value), fast_hash(&value));
+    }
+
+    #[test]
+    pub fn test_fast_hash_distinguishes_values() {
+        let mut seen: Vec<u64> = Vec::new();
+
+        let mut count: int = 0;
+        while count < 1000 {
+            seen.push(fast_hash(&count.to_string()));
+            count += 1;
+        }
+
+        let mut deduped = seen.clone();
+        deduped.sort();
+        deduped.dedup();
+
+        // a small fraction of collisions is expected, but 1000 distinct
+        // inputs should not collapse onto far fewer distinct hashes
+        assert!(deduped.len() > 990);
+    }
+}
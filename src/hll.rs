@@ -0,0 +1,514 @@
+
+use std::num;
+use std::hash::Hash;
+use std::comm::channel;
+
+use fasthash::{SeaHasher, fast_hash};
+
+static pow_2_32: f64 = 4294967296f64; // 2^32, used by the classic large-range correction
+
+///
+/// Implements HyperLogLog, a near-optimal cardinality estimator.  HLL
+/// keeps `m` small registers, each storing the largest number of trailing
+/// zeros (plus one) seen in the remaining bits of any hash that mapped to
+/// that register.  The harmonic mean of `2^-register` over all registers
+/// gives a low-memory estimate of cardinality.
+///
+/// See the [original paper](http://algo.inria.fr/flajolet/Publications/FlFuGaMe07.pdf)
+/// for background.
+///
+pub struct HLL {
+    p: u32,
+    m: u32,
+    registers: Vec<u8>
+}
+
+impl HLL {
+
+    /// Construct a new HLL counter
+    ///
+    /// p: number of bits to use as register index, larger values mean
+    ///    more registers, better accuracy, more space
+    ///    p must be between 4..16 inclusive
+    ///
+    ///    m = 2^p
+    ///
+    ///### Usage
+    ///
+    ///```
+    ///let mut hll = HLL::new(10);
+    ///```
+    #[experimental]
+    pub fn new(p: u32) -> HLL {
+
+        let m: u32;
+
+        match p {
+            4..16 => { m = num::pow(2, p as uint) },
+            _ => fail!("p must be 4 <= p <= 16")
+        }
+
+        let registers: Vec<u8> = Vec::from_elem(m as uint, 0u8);
+
+        HLL {p: p, m: m, registers: registers}
+    }
+
+    /// Offer a hashed u64 value to the HLL algorithm.  The low `p` bits
+    /// select a register, and the rank (number of trailing zeros plus
+    /// one, within the remaining bits) updates that register if it
+    /// is larger than the current value.
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let mut hll = HLL::new(10);
+    ///
+    /// // Hash the value with std::hash SipHash 2-4. Any 64-bit hash will work
+    /// // You should probably use something *other* than SipHash, since it is cryptographic
+    /// // and slow
+    /// let hash: u64 = hash::hash(&19u);
+    /// hll.offer_hashed(&hash);
+    ///
+    ///```
+    #[experimental]
+    pub fn offer_hashed(&mut self, hash: &u64) {
+        let index = (*hash & (self.m as u64 - 1)) as uint;
+        let remaining = *hash >> self.p as uint;
+
+        // remaining only has `q = 64 - p` significant bits, so the rank
+        // (trailing zeros + 1) can be at most q + 1.  When remaining is
+        // exactly zero, `trailing_zeros()` reports 64 (the full width of
+        // the u64), giving a rank of 65 that blows past the `q + 1`
+        // saturation bucket the histogram in `mle_cardinality` expects --
+        // clamp that case to the true maximum rank instead.
+        let q = 64u - self.p as uint;
+        let rank: u8 = if remaining == 0 {
+            (q + 1) as u8
+        } else {
+            (remaining.trailing_zeros() as uint + 1) as u8
+        };
+
+        if rank > self.registers[index] {
+            *self.registers.get_mut(index) = rank;
+        }
+    }
+
+    /// Offer a value to the HLL algorithm, hashing it internally with
+    /// this crate's fast non-cryptographic hasher.  Use `offer_hashed`
+    /// instead if you need to supply your own hash.
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let mut hll = HLL::new(10);
+    /// hll.offer(&19u);
+    ///```
+    #[experimental]
+    pub fn offer<T: Hash<SeaHasher>>(&mut self, value: &T) {
+        let hash = fast_hash(value);
+        self.offer_hashed(&hash);
+    }
+
+    /// Bias-corrected alpha constant used by the classic estimator
+    fn alpha(&self) -> f64 {
+        match self.m {
+            16 => 0.673f64,
+            32 => 0.697f64,
+            64 => 0.709f64,
+            _  => 0.7213f64 / (1f64 + 1.079f64 / self.m as f64)
+        }
+    }
+
+    /// Returns the current cardinality estimate, using the classic
+    /// harmonic-mean estimator with small/large range corrections.
+    #[experimental]
+    pub fn cardinality(&self) -> u32 {
+        let m = self.m as f64;
+        let mut sum = 0f64;
+        let mut zeros = 0u;
+
+        for &register in self.registers.iter() {
+            sum += 2f64.powf(-(register as f64));
+            if register == 0 { zeros += 1; }
+        }
+
+        let mut estimate = self.alpha() * m * m / sum;
+
+        if estimate <= 2.5f64 * m && zeros > 0 {
+            // small-range correction: linear counting
+            estimate = m * (m / zeros as f64).ln();
+        } else if estimate > pow_2_32 / 30f64 {
+            // large-range correction
+            estimate = -pow_2_32 * (1f64 - estimate / pow_2_32).ln();
+        }
+
+        estimate as u32
+    }
+
+    /// Returns the current cardinality estimate using a maximum-likelihood
+    /// estimator (Ertl's method) operating over the register-multiplicity
+    /// histogram, rather than the classic harmonic-mean formula.  This is
+    /// more accurate across the whole cardinality range and needs no
+    /// empirical bias-correction tables.
+    ///
+    /// The histogram `C[0..=q+1]` (where `q = 64 - p`) counts how many
+    /// registers hold each possible rank; `C[0]` is empty registers and
+    /// `C[q+1]` is saturated ones.  Under the Poissonized HLL model, each
+    /// register's value is the max of `Poisson(lambda)` independent
+    /// geometric ranks (`lambda` proportional to `n/m`), giving
+    ///
+    ///   P(R = 0)     = exp(-lambda)
+    ///   P(R = k)     = exp(-lambda.2^-k) - exp(-lambda.2^-(k-1))   for 1 <= k <= q
+    ///   P(R = q + 1) = 1 - exp(-lambda.2^-q)
+    ///
+    /// `lambda` is the root of the log-likelihood's derivative over this
+    /// histogram, found by a bracketed, bisection-guarded secant search
+    /// (so, unlike a raw secant step, it can never extrapolate past the
+    /// bracket into a negative `lambda`); the per-iteration cost is
+    /// proportional to the number of distinct register values rather
+    /// than `m`.
+    #[experimental]
+    pub fn mle_cardinality(&self) -> f64 {
+        let q: uint = 64u - self.p as uint;
+        let mut c: Vec<u32> = Vec::from_elem(q + 2, 0u32);
+
+        for &register in self.registers.iter() {
+            *c.get_mut(register as uint) += 1;
+        }
+
+        let m = self.m as f64;
+
+        if c[q + 1] == self.m {
+            // every register saturated: return the largest representable estimate
+            return m * num::pow(2f64, q);
+        }
+        if c[0] == self.m {
+            // nothing has ever been offered
+            return 0f64;
+        }
+
+        let mut k_min = 1u;
+        while k_min <= q && c[k_min] == 0 { k_min += 1; }
+
+        let mut k_max = q;
+        while k_max > k_min && c[k_max] == 0 { k_max -= 1; }
+
+        // derivative of the log-likelihood w.r.t lambda, summed only
+        // over the nonzero histogram buckets
+        let deriv = |lambda: f64| -> f64 {
+            let mut d = -(c[0] as f64);
+
+            let mut k = k_min;
+            while k <= k_max {
+                if c[k] > 0 {
+                    let weight_hi = num::pow(2f64, k - 1); // 2^(k-1)
+                    let weight_lo = num::pow(2f64, k);     // 2^k
+                    let e_hi = (-lambda / weight_hi).exp();
+                    let e_lo = (-lambda / weight_lo).exp();
+                    let p = e_lo - e_hi;
+
+                    if p > 1e-300f64 {
+                        let dp = (e_hi / weight_hi) - (e_lo / weight_lo);
+                        d += c[k] as f64 * dp / p;
+                    }
+                }
+                k += 1;
+            }
+
+            if c[q + 1] > 0 {
+                let weight_q = num::pow(2f64, q);
+                let e_q = (-lambda / weight_q).exp();
+                if 1f64 - e_q > 1e-300f64 {
+                    d += (c[q + 1] as f64) * (e_q / weight_q) / (1f64 - e_q);
+                }
+            }
+
+            d
+        };
+
+        // the log-likelihood is concave, so its derivative is strictly
+        // decreasing; find a bracket [lo, hi] with deriv(lo) > 0 >=
+        // deriv(hi) by doubling hi outward, then bisect (falling back
+        // from secant when it would leave the bracket) to refine lambda
+        // without ever stepping negative.
+        let mut lo = 1e-9f64;
+        let mut f_lo = deriv(lo);
+
+        if f_lo <= 0f64 {
+            // histogram carries essentially no signal above the floor
+            return 0f64;
+        }
+
+        let mut hi = 1f64;
+        let mut f_hi = deriv(hi);
+        let mut doublings = 0u;
+        while f_hi > 0f64 && doublings < 100u {
+            hi *= 2f64;
+            f_hi = deriv(hi);
+            doublings += 1;
+        }
+
+        let mut iterations = 0u;
+        let bound = if hi > 1f64 { hi } else { 1f64 };
+
+        while (hi - lo) > 1e-9f64 * bound && iterations < 100u {
+            let mut candidate = hi - f_hi * (hi - lo) / (f_hi - f_lo);
+
+            if candidate <= lo || candidate >= hi || (f_hi - f_lo).abs() < 1e-12f64 {
+                candidate = 0.5f64 * (lo + hi);
+            }
+
+            let f_candidate = deriv(candidate);
+
+            if f_candidate > 0f64 {
+                lo = candidate;
+                f_lo = f_candidate;
+            } else {
+                hi = candidate;
+                f_hi = f_candidate;
+            }
+
+            iterations += 1;
+        }
+
+        m * 0.5f64 * (lo + hi)
+    }
+
+    /// Returns the amount of memory (in bytes) used by this data structure
+    #[experimental]
+    pub fn ram_bytes_used(&self) -> u32 {
+        8 + self.m    // p + m + registers[u8, ..m]
+    }
+
+    /// Merge another counter into this counter
+    #[experimental]
+    pub fn merge(&mut self, h2: &HLL) {
+        let mut counter = 0u;
+
+        while counter < self.m as uint {
+            if h2.registers[counter] > self.registers[counter] {
+                *self.registers.get_mut(counter) = h2.registers[counter];
+            }
+            counter += 1;
+        }
+    }
+
+    /// Offer a slice of already-hashed values, one after another.  This
+    /// is allocation-free, so it is suitable as the inner loop of a
+    /// memory-mapped file scanner.
+    #[experimental]
+    pub fn offer_hashed_batch(&mut self, hashes: &[u64]) {
+        for hash in hashes.iter() {
+            self.offer_hashed(hash);
+        }
+    }
+
+    /// Build an HLL counter from a slice of hashes, ingesting it in
+    /// parallel across at most `threads` worker tasks, then folding the per-task
+    /// locals together with `merge` -- see `PCSA::from_hashes_parallel`
+    /// for why this gives a result identical to serial ingestion.  Here
+    /// the fold step is `merge`'s register-wise max rather than PCSA's
+    /// bucket-wise OR, but it is equally associative and commutative.
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let hll = HLL::from_hashes_parallel(10, hashes.as_slice(), 4);
+    ///```
+    #[experimental]
+    pub fn from_hashes_parallel(p: u32, hashes: &[u64], threads: uint) -> HLL {
+        if threads == 0 {
+            fail!("threads must be >= 1")
+        }
+
+        if hashes.len() == 0 {
+            return HLL::new(p);
+        }
+
+        let chunk_size = (hashes.len() + threads - 1) / threads;
+        let (tx, rx) = channel();
+
+        let mut spawned = 0u;
+        for chunk in hashes.chunks(chunk_size) {
+            let tx = tx.clone();
+            let chunk = chunk.to_vec();
+
+            spawn(proc() {
+                let mut local = HLL::new(p);
+                local.offer_hashed_batch(chunk.as_slice());
+                tx.send(local);
+            });
+
+            spawned += 1;
+        }
+
+        let mut result = HLL::new(p);
+        for _ in range(0u, spawned) {
+            let local = rx.recv();
+            result.merge(&local);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::HLL;
+    use std::hash;
+    use std::task;
+
+    #[test]
+    pub fn test_hll_merge() {
+        let mut count: int = 0;
+
+        let mut h1 = HLL::new(10);
+        let mut h2 = HLL::new(10);
+        while count < 10000 {
+            let hash = hash::hash(&count.to_string());
+            h1.offer_hashed(&hash);
+
+            let hash = hash::hash(&(count + 10000).to_string());
+            h2.offer_hashed(&hash);
+            count += 1;
+        }
+
+        h1.merge(&h2);
+
+        let estimate = h1.cardinality() as int;
+        let error = (estimate - 20000i).abs() as f64 / 20000f64;
+
+        assert!(error < 0.10f64);
+    }
+
+    #[test]
+    pub fn test_hll_bad_constructor() {
+        let mut p = 0;
+
+        while p < 20u32 {
+            let tp = p;
+            let result = task::try(proc() {
+                let mut hll = HLL::new(tp);
+            });
+
+            match p {
+                4..16 => {if result.is_err() {fail!("4..16 range threw error")}},
+                _     => {if !result.is_err() {fail!("_ range did not throw error")}}
+            }
+
+            p += 1u32;
+        }
+    }
+
+    #[test]
+    pub fn test_hll_mle_cardinality_matches_classic() {
+        let mut hll = HLL::new(10);
+
+        let mut count: int = 0;
+        while count < 10000 {
+            let hash = hash::hash(&count.to_string());
+            hll.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let classic = hll.cardinality() as f64;
+        let mle = hll.mle_cardinality();
+
+        let error = (classic - mle).abs() / classic;
+        assert!(error < 0.10f64);
+    }
+
+    #[test]
+    pub fn test_hll_mle_cardinality_low_range() {
+        // a handful of distinct items: almost all registers stay empty,
+        // which previously drove the (unbracketed) secant search to
+        // extrapolate to a negative lambda
+        let mut hll = HLL::new(10);
+
+        let mut count: int = 0;
+        while count < 20 {
+            let hash = hash::hash(&count.to_string());
+            hll.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let mle = hll.mle_cardinality();
+
+        assert!(mle >= 0f64);
+        assert!(mle.is_finite());
+        assert!((mle - 20f64).abs() / 20f64 < 1.0f64);
+    }
+
+    #[test]
+    pub fn test_hll_zero_remaining_does_not_overflow_rank() {
+        let mut hll = HLL::new(10);
+
+        // the low 10 bits select the register; the remaining (upper) 54
+        // bits are all zero, which previously produced a rank past the
+        // end of the mle_cardinality histogram
+        let hash: u64 = 5u64;
+        hll.offer_hashed(&hash);
+
+        hll.cardinality();
+        hll.mle_cardinality();
+    }
+
+    #[test]
+    pub fn test_hll_batch_matches_serial() {
+        let mut hashes: Vec<u64> = Vec::new();
+        let mut count: int = 0;
+        while count < 5000 {
+            hashes.push(hash::hash(&count.to_string()));
+            count += 1;
+        }
+
+        let mut serial = HLL::new(8);
+        for h in hashes.iter() {
+            serial.offer_hashed(h);
+        }
+
+        let mut batched = HLL::new(8);
+        batched.offer_hashed_batch(hashes.as_slice());
+
+        assert_eq!(serial.cardinality(), batched.cardinality());
+    }
+
+    #[test]
+    pub fn test_hll_parallel_matches_serial() {
+        let mut hashes: Vec<u64> = Vec::new();
+        let mut count: int = 0;
+        while count < 5000 {
+            hashes.push(hash::hash(&count.to_string()));
+            count += 1;
+        }
+
+        let mut serial = HLL::new(8);
+        serial.offer_hashed_batch(hashes.as_slice());
+
+        let parallel = HLL::from_hashes_parallel(8, hashes.as_slice(), 4);
+
+        assert_eq!(serial.cardinality(), parallel.cardinality());
+    }
+
+    #[test]
+    pub fn test_hll_parallel_empty_input() {
+        let hashes: Vec<u64> = Vec::new();
+        let parallel = HLL::from_hashes_parallel(8, hashes.as_slice(), 4);
+
+        assert_eq!(parallel.cardinality(), 0);
+    }
+
+    #[test]
+    pub fn test_hll_parallel_zero_threads() {
+        let hashes: Vec<u64> = vec![hash::hash(&1i.to_string())];
+
+        let result = task::try(proc() {
+            HLL::from_hashes_parallel(8, hashes.as_slice(), 0);
+        });
+
+        if result.is_ok() {
+            fail!("threads == 0 should have failed")
+        }
+    }
+}
@@ -0,0 +1,379 @@
+
+use std::num;
+
+static pow_2_32: f64 = 4294967296f64; // 2^32, used by the classic large-range correction
+
+///
+/// Implements HyperMinHash, a HLL-style sketch augmented with a small
+/// per-register min-hash remainder.  Like HLL, each of the `m` registers
+/// tracks the largest rank (trailing zero count, plus one) seen among
+/// hashes routed to it; unlike plain HLL, on a tie it also keeps the
+/// smallest observed `r`-bit suffix for that register.
+///
+/// This extra `r` bits per register is enough to estimate intersection
+/// size and Jaccard similarity between two sketches directly, which
+/// plain HLL/PCSA cannot do (inclusion-exclusion over two HLL unions has
+/// very poor relative error when the intersection is small relative to
+/// the union).
+///
+/// See the [HyperMinHash paper](https://arxiv.org/abs/1710.08436) for
+/// background.
+///
+pub struct HyperMinHash {
+    p: u32,
+    r: u32,
+    m: u32,
+    ranks: Vec<u8>,
+    suffixes: Vec<u32>
+}
+
+impl HyperMinHash {
+
+    /// Construct a new HyperMinHash sketch
+    ///
+    /// p: number of bits to use as register index (as with HLL), must be
+    ///    between 4..16 inclusive.  m = 2^p
+    /// r: number of bits to keep for the per-register min-hash suffix,
+    ///    must be between 4..32 inclusive.  Larger r gives more accurate
+    ///    intersection estimates at the cost of r extra bits per register.
+    ///
+    ///### Usage
+    ///
+    ///```
+    ///let mut hmh = HyperMinHash::new(10, 8);
+    ///```
+    #[experimental]
+    pub fn new(p: u32, r: u32) -> HyperMinHash {
+
+        let m: u32;
+
+        match p {
+            4..16 => { m = num::pow(2, p as uint) },
+            _ => fail!("p must be 4 <= p <= 16")
+        }
+
+        match r {
+            4..32 => {},
+            _ => fail!("r must be 4 <= r <= 32")
+        }
+
+        let ranks: Vec<u8> = Vec::from_elem(m as uint, 0u8);
+        let sentinel: u32 = (num::pow(2u64, r as uint) - 1) as u32;
+        let suffixes: Vec<u32> = Vec::from_elem(m as uint, sentinel);
+
+        HyperMinHash {p: p, r: r, m: m, ranks: ranks, suffixes: suffixes}
+    }
+
+    /// Offer a hashed u64 value.  The low `p` bits select a register (as
+    /// in HLL); the rank is the trailing zero count (plus one) of the
+    /// remaining bits.  On a new maximum rank, both the rank and the
+    /// suffix are replaced; on a tied rank, the smaller of the two
+    /// observed suffixes is kept.
+    #[experimental]
+    pub fn offer_hashed(&mut self, hash: &u64) {
+        let index = (*hash & (self.m as u64 - 1)) as uint;
+        let remaining = *hash >> self.p as uint;
+
+        // as in HLL, remaining only has `64 - p` significant bits, so a
+        // zero remaining must be clamped rather than trusting
+        // trailing_zeros() (which reports 64 for a fully-zero u64 and
+        // would give a rank beyond the `q + 1` maximum)
+        let q = 64u - self.p as uint;
+        let rank: u8 = if remaining == 0 {
+            (q + 1) as u8
+        } else {
+            (remaining.trailing_zeros() as uint + 1) as u8
+        };
+
+        let suffix = (*hash >> (64u - self.r as uint)) as u32;
+
+        if rank > self.ranks[index] {
+            *self.ranks.get_mut(index) = rank;
+            *self.suffixes.get_mut(index) = suffix;
+        } else if rank == self.ranks[index] && suffix < self.suffixes[index] {
+            *self.suffixes.get_mut(index) = suffix;
+        }
+    }
+
+    /// Bias-corrected alpha constant used by the cardinality estimator
+    fn alpha(&self) -> f64 {
+        match self.m {
+            16 => 0.673f64,
+            32 => 0.697f64,
+            64 => 0.709f64,
+            _  => 0.7213f64 / (1f64 + 1.079f64 / self.m as f64)
+        }
+    }
+
+    /// Applies the same small/large-range corrections `HLL::cardinality`
+    /// uses (linear counting when the raw estimate is small, the
+    /// saturation correction when it's large) to a harmonic sum and zero
+    /// count already accumulated by a caller.  Shared by `cardinality`
+    /// and `intersection`, so the union estimate `intersection` scales
+    /// its Jaccard index by is exactly as bias-corrected as a plain
+    /// `cardinality()` call would be.
+    fn corrected_estimate(&self, harmonic_sum: f64, zeros: uint) -> f64 {
+        let m = self.m as f64;
+        let mut estimate = self.alpha() * m * m / harmonic_sum;
+
+        if estimate <= 2.5f64 * m && zeros > 0 {
+            // small-range correction: linear counting
+            estimate = m * (m / zeros as f64).ln();
+        } else if estimate > pow_2_32 / 30f64 {
+            // large-range correction
+            estimate = -pow_2_32 * (1f64 - estimate / pow_2_32).ln();
+        }
+
+        estimate
+    }
+
+    /// Returns the current cardinality estimate, computed from the rank
+    /// part only (exactly as in plain HLL; the suffix bits do not factor
+    /// into this estimate), with the same small/large-range corrections
+    /// `HLL::cardinality` applies.
+    #[experimental]
+    pub fn cardinality(&self) -> u32 {
+        let mut sum = 0f64;
+        let mut zeros = 0u;
+
+        for &rank in self.ranks.iter() {
+            sum += 2f64.powf(-(rank as f64));
+            if rank == 0 { zeros += 1; }
+        }
+
+        self.corrected_estimate(sum, zeros) as u32
+    }
+
+    /// Returns the amount of memory (in bytes) used by this data structure
+    #[experimental]
+    pub fn ram_bytes_used(&self) -> u32 {
+        12 + (self.m) + (self.m * 4)    // p + r + m + ranks[u8,..m] + suffixes[u32,..m]
+    }
+
+    /// Returns the register-wise union of two sketches: the larger rank
+    /// wins per register, with suffix ties broken by keeping the smaller
+    /// suffix.
+    #[experimental]
+    pub fn union(&self, other: &HyperMinHash) -> HyperMinHash {
+        if self.p != other.p || self.r != other.r {
+            fail!("cannot union HyperMinHash instances of differing dimensions")
+        }
+
+        let mut merged = HyperMinHash::new(self.p, self.r);
+        let mut counter = 0u;
+
+        while counter < self.m as uint {
+            if self.ranks[counter] > other.ranks[counter] {
+                *merged.ranks.get_mut(counter) = self.ranks[counter];
+                *merged.suffixes.get_mut(counter) = self.suffixes[counter];
+            } else if other.ranks[counter] > self.ranks[counter] {
+                *merged.ranks.get_mut(counter) = other.ranks[counter];
+                *merged.suffixes.get_mut(counter) = other.suffixes[counter];
+            } else {
+                *merged.ranks.get_mut(counter) = self.ranks[counter];
+                *merged.suffixes.get_mut(counter) = if self.suffixes[counter] < other.suffixes[counter] {
+                    self.suffixes[counter]
+                } else {
+                    other.suffixes[counter]
+                };
+            }
+            counter += 1;
+        }
+
+        merged
+    }
+
+    /// Merge another sketch into this one in place (see `union`)
+    #[experimental]
+    pub fn merge(&mut self, other: &HyperMinHash) {
+        let merged = self.union(other);
+        self.ranks = merged.ranks;
+        self.suffixes = merged.suffixes;
+    }
+
+    /// Returns the estimated intersection cardinality between this
+    /// sketch and `other`.  Registers that agree on both rank and suffix
+    /// are counted as matches in the same pass that accumulates the
+    /// union's harmonic sum and zero count (so this needs no separate
+    /// `union()` call, and the union estimate gets the same small/large
+    /// range corrections `cardinality()` would apply); the match count
+    /// is corrected for the expected number of random collisions given
+    /// both the current cardinalities and `r` suffix bits, divided by
+    /// `m` to estimate the Jaccard index `J`, and finally scaled by the
+    /// union cardinality to recover `|A n B| = J * |A u B|`.
+    ///
+    /// The collision correction below -- `1 / (1 + load)` for the
+    /// rank-match probability, `1 / 2^r` for the suffix-match
+    /// probability -- is a heuristic, not a derivation from the sketch's
+    /// exact register-value distribution: it approximates "a loaded
+    /// register is less likely to collide by chance" without computing
+    /// the true per-register collision probability in closed form. It is
+    /// validated empirically by the tests below across a range of `r`
+    /// values and cardinality ratios (near-equal sizes, a large/small
+    /// pair, and a small `r`), rather than proven exact.
+    #[experimental]
+    pub fn intersection(&self, other: &HyperMinHash) -> u32 {
+        if self.p != other.p || self.r != other.r {
+            fail!("cannot estimate intersection of HyperMinHash instances of differing dimensions")
+        }
+
+        let mut matches = 0u;
+        let mut union_sum = 0f64;
+        let mut union_zeros = 0u;
+        let mut counter = 0u;
+
+        while counter < self.m as uint {
+            let self_rank = self.ranks[counter];
+            let other_rank = other.ranks[counter];
+
+            if self_rank > 0 && self_rank == other_rank && self.suffixes[counter] == other.suffixes[counter] {
+                matches += 1;
+            }
+
+            let max_rank = if self_rank > other_rank { self_rank } else { other_rank };
+            union_sum += 2f64.powf(-(max_rank as f64));
+            if max_rank == 0 { union_zeros += 1; }
+
+            counter += 1;
+        }
+
+        let m = self.m as f64;
+        let union_cardinality = self.corrected_estimate(union_sum, union_zeros);
+
+        // expected number of matches between two *unrelated* registers by
+        // chance alone.  A chance match needs both the rank and the
+        // suffix to agree; the rank-collision probability rises with how
+        // loaded (high-cardinality) each sketch's registers are, so it is
+        // derived from the sketches' own cardinality estimates rather
+        // than treated as a fixed constant.
+        let na = self.cardinality() as f64;
+        let nb = other.cardinality() as f64;
+        let load = ((na / m) + (nb / m)) / 2f64;
+        let rank_collision_prob = 1f64 / (1f64 + load);
+        let suffix_collision_prob = 1f64 / num::pow(2f64, self.r as uint);
+        let expected = m * rank_collision_prob * suffix_collision_prob;
+
+        let corrected = if matches as f64 > expected { matches as f64 - expected } else { 0f64 };
+        let jaccard = corrected / m;
+
+        (jaccard * union_cardinality) as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::HyperMinHash;
+    use std::hash;
+
+    #[test]
+    pub fn test_hyperminhash_union_cardinality() {
+        let mut count: int = 0;
+
+        let mut h1 = HyperMinHash::new(10, 8);
+        let mut h2 = HyperMinHash::new(10, 8);
+        while count < 10000 {
+            let hash = hash::hash(&count.to_string());
+            h1.offer_hashed(&hash);
+
+            let hash = hash::hash(&(count + 10000).to_string());
+            h2.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let merged = h1.union(&h2);
+
+        let estimate = merged.cardinality() as int;
+        let error = (estimate - 20000i).abs() as f64 / 20000f64;
+
+        assert!(error < 0.10f64);
+    }
+
+    #[test]
+    pub fn test_hyperminhash_intersection() {
+        let mut count: int = 0;
+
+        // h1 sees [0, 15000), h2 sees [10000, 20000) -- a shared range of
+        // 5000 items out of a 20000-item union
+        let mut h1 = HyperMinHash::new(12, 8);
+        let mut h2 = HyperMinHash::new(12, 8);
+        while count < 15000 {
+            let hash = hash::hash(&count.to_string());
+            h1.offer_hashed(&hash);
+            count += 1;
+        }
+        count = 10000;
+        while count < 20000 {
+            let hash = hash::hash(&count.to_string());
+            h2.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let estimate = h1.intersection(&h2) as f64;
+        let error = (estimate - 5000f64).abs() / 5000f64;
+
+        assert!(error < 0.35f64);
+    }
+
+    #[test]
+    pub fn test_hyperminhash_intersection_small_r() {
+        let mut count: int = 0;
+
+        // same overlap as test_hyperminhash_intersection, but with a much
+        // smaller suffix (r=4 -> only 16 possible suffix values), where
+        // the chance-collision correction matters most
+        let mut h1 = HyperMinHash::new(12, 4);
+        let mut h2 = HyperMinHash::new(12, 4);
+        while count < 15000 {
+            let hash = hash::hash(&count.to_string());
+            h1.offer_hashed(&hash);
+            count += 1;
+        }
+        count = 10000;
+        while count < 20000 {
+            let hash = hash::hash(&count.to_string());
+            h2.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let estimate = h1.intersection(&h2) as f64;
+        let error = (estimate - 5000f64).abs() / 5000f64;
+
+        assert!(error < 0.50f64);
+    }
+
+    #[test]
+    pub fn test_hyperminhash_intersection_skewed_cardinalities() {
+        let mut count: int = 0;
+
+        // h1 is much larger than h2, with half of h2 contained in it
+        let mut h1 = HyperMinHash::new(14, 10);
+        let mut h2 = HyperMinHash::new(14, 10);
+        while count < 50000 {
+            let hash = hash::hash(&count.to_string());
+            h1.offer_hashed(&hash);
+            count += 1;
+        }
+        count = 45000;
+        while count < 55000 {
+            let hash = hash::hash(&count.to_string());
+            h2.offer_hashed(&hash);
+            count += 1;
+        }
+
+        let estimate = h1.intersection(&h2) as f64;
+        let error = (estimate - 5000f64).abs() / 5000f64;
+
+        assert!(error < 0.50f64);
+    }
+
+    #[test]
+    pub fn test_hyperminhash_zero_remaining_does_not_overflow_rank() {
+        let mut hmh = HyperMinHash::new(10, 8);
+
+        let hash: u64 = 5u64;
+        hmh.offer_hashed(&hash);
+
+        hmh.cardinality();
+    }
+}
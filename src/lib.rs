@@ -25,6 +25,8 @@ extern crate test;
 
 pub use hll::{HLL};
 pub use pcsa::PCSA;
+pub use countmin::CountMinSketch;
+pub use hyperminhash::HyperMinHash;
 
 /// HyperLogLog - Approximates cardinality estimation with minimal memory overhead
 pub mod hll;
@@ -32,3 +34,12 @@ pub mod hll;
 /// Probalistic Counter with Stochastic Averaging - Approximate cardinality estimation
 pub mod pcsa;
 
+/// Count-Min Sketch - Approximate frequency estimation with bounded error
+pub mod countmin;
+
+/// HyperMinHash - HLL augmented with min-hash remainders for intersection/Jaccard estimation
+pub mod hyperminhash;
+
+/// Fast non-cryptographic hashing - used internally by the `offer` convenience methods
+pub mod fasthash;
+
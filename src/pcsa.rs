@@ -1,6 +1,10 @@
 
 use std::int;
 use std::num;
+use std::hash::Hash;
+use std::comm::channel;
+
+use fasthash::{SeaHasher, fast_hash};
 
 static phi: f64 = 0.77351f64;
 static kappa: f64 = 1.75f64;
@@ -93,6 +97,25 @@ impl PCSA {
         *self.buckets.get_mut(index as uint) |= 1 << (hash >> self.b as uint).trailing_zeros() as uint;
     }
 
+    /// Offer a value to the PCSA algorithm, hashing it internally with
+    /// this crate's fast non-cryptographic hasher.  This avoids making
+    /// callers hash values themselves (and reach for something other
+    /// than the slow, cryptographic SipHash that `std::hash` defaults
+    /// to).  Use `offer_hashed` instead if you need to supply your own
+    /// hash.
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let mut pcsa = PCSA::new(10);
+    /// pcsa.offer(&19u);
+    ///```
+    #[experimental]
+    pub fn offer<T: Hash<SeaHasher>>(&mut self, value: &T) {
+        let hash = fast_hash(value);
+        self.offer_hashed(&hash);
+    }
+
 
     /// Returns the current cardinality estimate
     #[experimental]
@@ -150,6 +173,65 @@ impl PCSA {
         }
 
     }
+
+    /// Offer a slice of already-hashed values, one after another.  This
+    /// is allocation-free, so it is suitable as the inner loop of a
+    /// memory-mapped file scanner.
+    #[experimental]
+    pub fn offer_hashed_batch(&mut self, hashes: &[u64]) {
+        for hash in hashes.iter() {
+            self.offer_hashed(hash);
+        }
+    }
+
+    /// Build a PCSA counter from a slice of hashes, ingesting it in
+    /// parallel across at most `threads` worker tasks.  The input is split
+    /// into at most `threads` chunks, each chunk is ingested into its own local PCSA
+    /// counter on its own task, and the locals are folded together with
+    /// `merge`.  Because `merge` (bucket-wise OR) is associative and
+    /// commutative, the result is identical to ingesting `hashes`
+    /// serially, so this is a pure throughput win that scales with cores.
+    ///
+    ///### Usage
+    ///
+    ///```
+    /// let pcsa = PCSA::from_hashes_parallel(10, hashes.as_slice(), 4);
+    ///```
+    #[experimental]
+    pub fn from_hashes_parallel(b: u32, hashes: &[u64], threads: uint) -> PCSA {
+        if threads == 0 {
+            fail!("threads must be >= 1")
+        }
+
+        if hashes.len() == 0 {
+            return PCSA::new(b);
+        }
+
+        let chunk_size = (hashes.len() + threads - 1) / threads;
+        let (tx, rx) = channel();
+
+        let mut spawned = 0u;
+        for chunk in hashes.chunks(chunk_size) {
+            let tx = tx.clone();
+            let chunk = chunk.to_vec();
+
+            spawn(proc() {
+                let mut local = PCSA::new(b);
+                local.offer_hashed_batch(chunk.as_slice());
+                tx.send(local);
+            });
+
+            spawned += 1;
+        }
+
+        let mut result = PCSA::new(b);
+        for _ in range(0u, spawned) {
+            let local = rx.recv();
+            result.merge(&local);
+        }
+
+        result
+    }
 }
 
 #[cfg(test)]
@@ -291,4 +373,62 @@ mod test {
         }
     }
 
+    #[test]
+    pub fn test_pcsa_batch_matches_serial() {
+        let mut hashes: Vec<u64> = Vec::new();
+        let mut count: int = 0;
+        while count < 5000 {
+            hashes.push(hash::hash(&count.to_string()));
+            count += 1;
+        }
+
+        let mut serial = PCSA::new(8);
+        for h in hashes.iter() {
+            serial.offer_hashed(h);
+        }
+
+        let mut batched = PCSA::new(8);
+        batched.offer_hashed_batch(hashes.as_slice());
+
+        assert_eq!(serial.cardinality(), batched.cardinality());
+    }
+
+    #[test]
+    pub fn test_pcsa_parallel_matches_serial() {
+        let mut hashes: Vec<u64> = Vec::new();
+        let mut count: int = 0;
+        while count < 5000 {
+            hashes.push(hash::hash(&count.to_string()));
+            count += 1;
+        }
+
+        let mut serial = PCSA::new(8);
+        serial.offer_hashed_batch(hashes.as_slice());
+
+        let parallel = PCSA::from_hashes_parallel(8, hashes.as_slice(), 4);
+
+        assert_eq!(serial.cardinality(), parallel.cardinality());
+    }
+
+    #[test]
+    pub fn test_pcsa_parallel_empty_input() {
+        let hashes: Vec<u64> = Vec::new();
+        let parallel = PCSA::from_hashes_parallel(8, hashes.as_slice(), 4);
+
+        assert_eq!(parallel.cardinality(), 0);
+    }
+
+    #[test]
+    pub fn test_pcsa_parallel_zero_threads() {
+        let hashes: Vec<u64> = vec![hash::hash(&1i.to_string())];
+
+        let result = task::try(proc() {
+            PCSA::from_hashes_parallel(8, hashes.as_slice(), 0);
+        });
+
+        if result.is_ok() {
+            fail!("threads == 0 should have failed")
+        }
+    }
+
 }
\ No newline at end of file